@@ -0,0 +1,48 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::tx::VideoMetadata;
+
+pub const DEFAULT_CACHE_PATH: &str = "video_metadata_cache.json";
+
+/// Disk-backed cache of [`VideoMetadata`], keyed by the 11-character
+/// canonical YouTube video ID. Lets genesis re-sync skip a live metadata
+/// fetch for every video it has already seen.
+pub struct MetadataCache {
+    path: PathBuf,
+    entries: HashMap<String, VideoMetadata>,
+}
+
+impl MetadataCache {
+    /// Loads the cache from `path`, starting empty if the file doesn't exist
+    /// yet.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let entries = match std::fs::read(&path) {
+            Ok(bytes) => {
+                serde_json::from_slice(&bytes).context("failed to parse metadata cache file")?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e).context("failed to read metadata cache file"),
+        };
+
+        Ok(MetadataCache { path, entries })
+    }
+
+    pub fn get(&self, video_id: &str) -> Option<&VideoMetadata> {
+        self.entries.get(video_id)
+    }
+
+    pub fn insert(&mut self, video_id: String, metadata: VideoMetadata) {
+        self.entries.insert(video_id, metadata);
+    }
+
+    /// Flushes the cache to disk. Cheap enough to call after every miss, but
+    /// also invoked periodically by the fullnode so a crash doesn't lose
+    /// more than a cache flush interval's worth of lookups.
+    pub fn flush(&self) -> Result<()> {
+        let bytes = serde_json::to_vec_pretty(&self.entries)?;
+        std::fs::write(&self.path, bytes).context("failed to write metadata cache file")
+    }
+}
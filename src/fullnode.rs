@@ -6,21 +6,30 @@ use axum::{
 use celestia_rpc::{BlobClient, HeaderClient};
 use celestia_types::{nmt::Namespace, Blob, TxConfig};
 use log::*;
+use std::collections::HashSet;
+use std::env;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
 };
+use std::time::{Duration as StdDuration, SystemTime};
 use tokio::spawn;
 use tokio::sync::{mpsc, Mutex, Notify};
 use tokio::time::{interval, Duration};
 
-use crate::{state::State, tx::Transaction, webserver::*};
+use crate::{
+    cache::{MetadataCache, DEFAULT_CACHE_PATH},
+    state::State,
+    tx::Transaction,
+    webserver::*,
+};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize)]
 pub struct Batch(Vec<Transaction>);
 
 const BATCH_INTERVAL: Duration = Duration::from_secs(3);
+const CACHE_FLUSH_INTERVAL: Duration = Duration::from_secs(30);
 
 pub struct FullNode {
     da_client: celestia_rpc::Client,
@@ -57,22 +66,58 @@ impl FullNode {
             .await
             .context("Couldn't start Celestia client")?;
 
+        let metadata_cache =
+            MetadataCache::load(DEFAULT_CACHE_PATH).context("Couldn't load metadata cache")?;
+
+        let mut state = State::new(metadata_cache);
+        if let Ok(threshold) = env::var("MUSICNODE_SKIP_VOTE_THRESHOLD") {
+            let threshold = threshold
+                .parse()
+                .context("Failed to parse MUSICNODE_SKIP_VOTE_THRESHOLD")?;
+            state.set_skip_vote_threshold(threshold);
+        }
+        if let Ok(moderators) = env::var("MUSICNODE_MODERATORS") {
+            let moderators = moderators
+                .split(',')
+                .filter(|key| !key.is_empty())
+                .map(hex::decode)
+                .collect::<Result<HashSet<_>, _>>()
+                .context("Failed to parse MUSICNODE_MODERATORS as comma-separated hex pubkeys")?;
+            state.set_moderators(moderators);
+        }
+
         Ok(FullNode {
             da_client,
             namespace,
             start_height,
             pending_transactions: Arc::new(Mutex::new(Vec::new())),
-            state: Arc::new(Mutex::new(State::new())),
+            state: Arc::new(Mutex::new(state)),
             genesis_sync_complete: Arc::new(AtomicBool::new(false)),
             sync_notify: Arc::new(Notify::new()),
         })
     }
 
+    pub async fn start_cache_flushing(self: Arc<Self>) {
+        let mut interval = interval(CACHE_FLUSH_INTERVAL);
+
+        loop {
+            interval.tick().await;
+            let state = self.state.lock().await;
+            if let Err(e) = state.metadata_cache().flush() {
+                error!("Error flushing metadata cache: {}", e);
+            }
+        }
+    }
+
     pub async fn start_server(self: Arc<Self>) -> Result<()> {
         let app = Router::new()
             .route("/channels", get(get_history))
             .route("/channels/:channel", get(get_queue))
             .route("/send", post(send_tx))
+            .route("/skip", post(skip))
+            .route("/remove", post(remove))
+            .route("/vote-skip", post(vote_skip))
+            .route("/feed.xml", get(get_feed))
             .with_state(self.clone());
 
         let addr = "0.0.0.0:3000";
@@ -107,7 +152,16 @@ impl FullNode {
         Ok(())
     }
 
-    async fn process_l1_block(self: Arc<Self>, blobs: Vec<Blob>) {
+    /// Fetches the header for `height` and returns its timestamp as a
+    /// `SystemTime`, so playback scheduling can be derived from the DA
+    /// history rather than each node's local wall clock.
+    async fn block_time(&self, height: u64) -> Result<SystemTime> {
+        let header = HeaderClient::header_get_by_height(&self.da_client, height).await?;
+        let time = header.header.time;
+        Ok(SystemTime::UNIX_EPOCH + StdDuration::from_secs(time.unix_timestamp().max(0) as u64))
+    }
+
+    async fn process_l1_block(self: Arc<Self>, block_time: SystemTime, blobs: Vec<Blob>) {
         let txs: Vec<Transaction> = blobs
             .into_iter()
             .flat_map(|blob| match Batch::try_from(&blob) {
@@ -120,6 +174,8 @@ impl FullNode {
             .collect();
 
         let mut state = self.state.lock().await;
+        state.advance_time(block_time);
+        state.cleanup_queue();
         for tx in txs {
             match state.process_tx(tx).await {
                 Ok(_) => info!("processed transaction"),
@@ -140,7 +196,8 @@ impl FullNode {
             let response =
                 BlobClient::blob_get_all(&self.da_client, height, &[self.namespace]).await?;
             if let Some(blobs) = response {
-                self.clone().process_l1_block(blobs).await;
+                let block_time = self.block_time(height).await?;
+                self.clone().process_l1_block(block_time, blobs).await;
             }
         }
         info!("completed historical block processing");
@@ -180,9 +237,8 @@ impl FullNode {
                                 blob_response.blobs.clone().unwrap_or(vec![]).len(),
                                 blob_response.height
                             );
-                            node.state.lock().await.cleanup_queue();
                             if let Some(blobs) = blob_response.blobs {
-                                if tx.send(blobs).await.is_err() {
+                                if tx.send((blob_response.height, blobs)).await.is_err() {
                                     break;
                                 }
                             }
@@ -199,9 +255,12 @@ impl FullNode {
         self.sync_notify.notified().await;
 
         // Process incoming blocks
-        while let Some(blobs) = rx.recv().await {
+        while let Some((height, blobs)) = rx.recv().await {
             info!("processing incoming blobs");
-            self.clone().process_l1_block(blobs).await;
+            match self.block_time(height).await {
+                Ok(block_time) => self.clone().process_l1_block(block_time, blobs).await,
+                Err(e) => error!("fetching block time: {}", e),
+            }
         }
 
         subscription_handle.await
@@ -239,7 +298,17 @@ impl FullNode {
             async move { node.start_server().await }
         });
 
-        let _ = tokio::try_join!(sync_handle, batch_posting_handle, server_handle)?;
+        let cache_flushing_handle = spawn({
+            let node = self.clone();
+            async move { node.start_cache_flushing().await }
+        });
+
+        let _ = tokio::try_join!(
+            sync_handle,
+            batch_posting_handle,
+            server_handle,
+            cache_flushing_handle
+        )?;
 
         Ok(())
     }
@@ -1,8 +1,11 @@
 use anyhow::{Context, Result};
 use celestia_types::nmt::Namespace;
+use ed25519_dalek::SigningKey;
+use rand::rngs::OsRng;
 use std::{env, sync::Arc};
-use tx::YoutubeLink;
+use tx::{PlaylistLink, SearchQuery, Transaction, TransactionPayload, YoutubeLink, DEFAULT_ENQUEUE_CAP};
 
+mod cache;
 mod fullnode;
 mod state;
 mod tx;
@@ -10,6 +13,8 @@ mod webserver;
 
 use crate::fullnode::FullNode;
 
+const DEFAULT_IDENTITY_PATH: &str = "identity.key";
+
 #[macro_use]
 extern crate log;
 
@@ -43,36 +48,94 @@ async fn main() -> Result<()> {
         }
         "add-song" => {
             if args.len() < 3 {
-                error!("URL required");
+                error!("URL, playlist URL, or search query required");
                 return Ok(());
             }
 
             let client = reqwest::Client::new();
-            let server_url =
-                env::var("MUSICNODE_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
+            let server_url = musicnode_url();
 
             add_song(&client, &server_url, &args[2]).await?;
         }
+        "skip" => {
+            let client = reqwest::Client::new();
+            let server_url = musicnode_url();
+
+            skip_song(&client, &server_url).await?;
+        }
+        "remove-song" => {
+            if args.len() < 3 {
+                error!("URL or video ID required");
+                return Ok(());
+            }
+
+            let client = reqwest::Client::new();
+            let server_url = musicnode_url();
+
+            remove_song(&client, &server_url, &args[2]).await?;
+        }
+        "vote-skip" => {
+            let client = reqwest::Client::new();
+            let server_url = musicnode_url();
+
+            vote_skip(&client, &server_url).await?;
+        }
         _ => print_usage(),
     }
 
     Ok(())
 }
 
+fn musicnode_url() -> String {
+    env::var("MUSICNODE_URL").unwrap_or_else(|_| "http://localhost:3000".to_string())
+}
+
 fn print_usage() {
     println!("Usage:");
     println!("  musicnode start-fullnode <start_height> <namespace_hex>");
-    println!("  musicnode add-song <youtube_url> <duration_secs>");
+    println!("  musicnode add-song <youtube_url|playlist_url|search query>");
+    println!("  musicnode skip");
+    println!("  musicnode remove-song <youtube_url>");
+    println!("  musicnode vote-skip");
 }
 
-async fn add_song(client: &reqwest::Client, server_url: &str, url: &str) -> Result<()> {
-    let link = YoutubeLink::new(url.to_string())?;
+fn enqueue_cap() -> usize {
+    env::var("MUSICNODE_ENQUEUE_CAP")
+        .ok()
+        .and_then(|cap| cap.parse().ok())
+        .unwrap_or(DEFAULT_ENQUEUE_CAP)
+}
+
+/// Dispatches a single video URL, a playlist URL, or a free-text search
+/// query, enqueuing one `AddToQueue` transaction per resolved video (capped
+/// by `MUSICNODE_ENQUEUE_CAP`).
+async fn add_song(client: &reqwest::Client, server_url: &str, input: &str) -> Result<()> {
+    if let Ok(link) = YoutubeLink::new(input.to_string()) {
+        return enqueue_video(client, server_url, link).await;
+    }
+
+    let cap = enqueue_cap();
+
+    let links = if let Ok(playlist) = PlaylistLink::new(input.to_string()) {
+        playlist.resolve(cap).await?
+    } else {
+        SearchQuery::new(input.to_string())?.resolve(cap).await?
+    };
+
+    info!("Resolved {} videos to enqueue", links.len());
+    for link in links {
+        enqueue_video(client, server_url, link).await?;
+    }
+    Ok(())
+}
+
+async fn enqueue_video(client: &reqwest::Client, server_url: &str, link: YoutubeLink) -> Result<()> {
+    let signing_key = load_or_create_identity(DEFAULT_IDENTITY_PATH)?;
+    let tx = Transaction::sign(TransactionPayload::AddToQueue { url: link }, &signing_key)?;
 
     let response = client
         .post(format!("{}/send", server_url))
-        .json(&serde_json::json!({
-            "url": link.as_str(),
-        }))
+        .json(&tx)
         .send()
         .await?;
 
@@ -86,3 +149,92 @@ async fn add_song(client: &reqwest::Client, server_url: &str, url: &str) -> Resu
     }
     Ok(())
 }
+
+async fn skip_song(client: &reqwest::Client, server_url: &str) -> Result<()> {
+    let signing_key = load_or_create_identity(DEFAULT_IDENTITY_PATH)?;
+    let tx = Transaction::sign(TransactionPayload::Skip, &signing_key)?;
+
+    let response = client
+        .post(format!("{}/skip", server_url))
+        .json(&tx)
+        .send()
+        .await?;
+
+    if response.status().is_success() {
+        info!("Song skipped.");
+    } else {
+        error!(
+            "Failed to skip song. Server responded with: {}",
+            response.status()
+        );
+    }
+    Ok(())
+}
+
+async fn remove_song(client: &reqwest::Client, server_url: &str, url: &str) -> Result<()> {
+    let link = YoutubeLink::new(url.to_string())?;
+    let video_id = link.video_id().to_string();
+
+    let signing_key = load_or_create_identity(DEFAULT_IDENTITY_PATH)?;
+    let tx = Transaction::sign(
+        TransactionPayload::RemoveFromQueue { video_id },
+        &signing_key,
+    )?;
+
+    let response = client
+        .post(format!("{}/remove", server_url))
+        .json(&tx)
+        .send()
+        .await?;
+
+    if response.status().is_success() {
+        info!("Song removed.");
+    } else {
+        error!(
+            "Failed to remove song. Server responded with: {}",
+            response.status()
+        );
+    }
+    Ok(())
+}
+
+async fn vote_skip(client: &reqwest::Client, server_url: &str) -> Result<()> {
+    let signing_key = load_or_create_identity(DEFAULT_IDENTITY_PATH)?;
+    let tx = Transaction::sign(TransactionPayload::VoteSkip, &signing_key)?;
+
+    let response = client
+        .post(format!("{}/vote-skip", server_url))
+        .json(&tx)
+        .send()
+        .await?;
+
+    if response.status().is_success() {
+        info!("Vote to skip recorded.");
+    } else {
+        error!(
+            "Failed to vote to skip. Server responded with: {}",
+            response.status()
+        );
+    }
+    Ok(())
+}
+
+/// Loads the local ed25519 identity used to sign outgoing transactions,
+/// generating and persisting one on first use.
+fn load_or_create_identity(path: &str) -> Result<SigningKey> {
+    match std::fs::read(path) {
+        Ok(bytes) => {
+            let bytes: [u8; 32] = bytes
+                .as_slice()
+                .try_into()
+                .context("identity key file is corrupt")?;
+            Ok(SigningKey::from_bytes(&bytes))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let signing_key = SigningKey::generate(&mut OsRng);
+            std::fs::write(path, signing_key.to_bytes()).context("failed to persist identity")?;
+            Ok(signing_key)
+        }
+        Err(e) => Err(e).context("failed to read identity key"),
+    }
+}
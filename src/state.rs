@@ -1,33 +1,102 @@
-use anyhow::{bail, Result};
-use std::collections::VecDeque;
+use anyhow::{anyhow, bail, Result};
+use std::collections::{HashSet, VecDeque};
 use std::time::{Duration, SystemTime};
 
 use serde::{Deserialize, Serialize};
 
+use crate::cache::MetadataCache;
 use crate::tx::Transaction;
+use crate::tx::TransactionPayload;
+use crate::tx::VideoMetadata;
 use crate::tx::YoutubeLink;
 
+/// Number of distinct submitters who must vote to skip the currently
+/// playing song before it's auto-skipped.
+const DEFAULT_SKIP_VOTE_THRESHOLD: usize = 3;
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct QueuedSong {
     pub start_time: SystemTime,
     pub duration: Duration,
     pub link: YoutubeLink,
+    pub title: Option<String>,
+    pub uploader: Option<String>,
+    pub thumbnail: Option<String>,
+    pub live_status: Option<String>,
+    /// Ed25519 public key of whoever submitted this song.
+    pub submitter: Vec<u8>,
+}
+
+impl QueuedSong {
+    fn new(
+        start_time: SystemTime,
+        link: YoutubeLink,
+        metadata: VideoMetadata,
+        submitter: Vec<u8>,
+    ) -> Self {
+        QueuedSong {
+            start_time,
+            duration: metadata.duration,
+            link,
+            title: metadata.title,
+            uploader: metadata.uploader,
+            thumbnail: metadata.thumbnail,
+            live_status: metadata.live_status,
+            submitter,
+        }
+    }
 }
 
 pub struct State {
     pub history: VecDeque<QueuedSong>,
     pub queue: VecDeque<QueuedSong>,
+    metadata_cache: MetadataCache,
+    // Timestamp of the latest processed Celestia block. Used as the anchor
+    // for scheduling instead of wall-clock time, so that replaying the same
+    // DA history always produces the same queue/history on every node.
+    current_time: SystemTime,
+    // Distinct submitters who have voted to skip the current front song.
+    // Cleared whenever the front song changes.
+    skip_votes: HashSet<Vec<u8>>,
+    skip_vote_threshold: usize,
+    // Submitters allowed to instantly `Skip` without going through the vote
+    // threshold. Empty by default, meaning nobody can bypass `VoteSkip`.
+    moderators: HashSet<Vec<u8>>,
 }
 
 #[allow(dead_code)]
 impl State {
-    pub fn new() -> Self {
+    pub fn new(metadata_cache: MetadataCache) -> Self {
         State {
             history: VecDeque::new(),
             queue: VecDeque::new(),
+            metadata_cache,
+            current_time: SystemTime::now(),
+            skip_votes: HashSet::new(),
+            skip_vote_threshold: DEFAULT_SKIP_VOTE_THRESHOLD,
+            moderators: HashSet::new(),
         }
     }
 
+    pub fn metadata_cache(&self) -> &MetadataCache {
+        &self.metadata_cache
+    }
+
+    pub fn set_skip_vote_threshold(&mut self, threshold: usize) {
+        self.skip_vote_threshold = threshold;
+    }
+
+    pub fn set_moderators(&mut self, moderators: HashSet<Vec<u8>>) {
+        self.moderators = moderators;
+    }
+
+    /// Advances the state's notion of "now" to the given Celestia block
+    /// timestamp. Must be called before processing the transactions in that
+    /// block, and before `cleanup_queue`.
+    pub fn advance_time(&mut self, block_time: SystemTime) {
+        self.current_time = block_time;
+    }
+
     pub fn get_next_song(&self) -> Option<&QueuedSong> {
         self.queue.front()
     }
@@ -44,12 +113,12 @@ impl State {
     // fullnode. bad architecture but we ball
     pub fn cleanup_queue(&mut self) {
         while let Some(song) = self.queue.front() {
-            let current_time = SystemTime::now();
             // Check if song has finished playing
-            if current_time.duration_since(song.start_time).unwrap() >= song.duration {
+            if self.current_time.duration_since(song.start_time).unwrap() >= song.duration {
                 // Use if let to avoid simultaneous borrows
                 if let Some(finished_song) = self.queue.pop_front() {
                     self.history.push_back(finished_song);
+                    self.skip_votes.clear();
                 }
             } else {
                 // Rest of songs in queue are in the future, no reason to loop anymore
@@ -58,34 +127,147 @@ impl State {
         }
     }
 
-    pub fn validate_tx(&self, tx: Transaction) -> Result<()> {
-        let Transaction::AddToQueue { url } = tx.clone();
-        let validated_link = YoutubeLink::new(url.as_str().to_string());
-        if validated_link.is_err() {
-            bail!("invalid tx: youtube link failed validation")
+    /// Verifies the transaction's signature and payload, returning the
+    /// submitter's verifying key on success. Must be called, and must
+    /// succeed, before the transaction is allowed to mutate the queue.
+    pub fn validate_tx(&self, tx: &Transaction) -> Result<Vec<u8>> {
+        let submitter = tx
+            .verify()
+            .map_err(|e| anyhow!("invalid tx: signature verification failed: {e}"))?
+            .to_bytes()
+            .to_vec();
+
+        match &tx.payload {
+            TransactionPayload::AddToQueue { url } => {
+                YoutubeLink::new(url.as_str().to_string())
+                    .map_err(|_| anyhow!("invalid tx: youtube link failed validation"))?;
+            }
+            TransactionPayload::RemoveFromQueue { video_id } => {
+                if video_id.len() != 11 {
+                    bail!("invalid tx: malformed video id");
+                }
+                if !self.moderators.contains(&submitter) {
+                    let owned_by_submitter = self
+                        .queue
+                        .iter()
+                        .find(|song| song.link.video_id() == video_id.as_str())
+                        .map_or(true, |song| song.submitter == submitter);
+                    if !owned_by_submitter {
+                        bail!("invalid tx: RemoveFromQueue requires moderator privileges or ownership of the song");
+                    }
+                }
+            }
+            TransactionPayload::Skip => {
+                if !self.moderators.contains(&submitter) {
+                    bail!("invalid tx: Skip requires moderator privileges, use VoteSkip instead");
+                }
+            }
+            TransactionPayload::VoteSkip => {}
         }
-        Ok(())
+
+        Ok(submitter)
     }
 
     pub async fn process_tx(&mut self, tx: Transaction) -> Result<()> {
-        self.validate_tx(tx.clone())?;
+        let submitter = self.validate_tx(&tx)?;
+
+        match tx.payload {
+            TransactionPayload::AddToQueue { url } => self.add_to_queue(url, submitter).await?,
+            TransactionPayload::RemoveFromQueue { video_id } => {
+                self.remove_from_queue(&video_id)
+            }
+            TransactionPayload::Skip => self.skip_front(),
+            TransactionPayload::VoteSkip => self.vote_skip(submitter),
+        }
+
+        Ok(())
+    }
 
+    async fn add_to_queue(&mut self, url: YoutubeLink, submitter: Vec<u8>) -> Result<()> {
         let new_start_time = self
             .queue
             .back()
             .map(|song| song.start_time + song.duration)
-            .unwrap_or(SystemTime::now());
+            .unwrap_or(self.current_time);
 
-        // this can only be done because we only have one tx type rn
-        let Transaction::AddToQueue { url } = tx;
-        let duration = url.get_video_duration().await?;
+        let video_id = url.video_id().to_string();
+        let metadata = match self.metadata_cache.get(&video_id) {
+            Some(cached) => cached.clone(),
+            None => {
+                let metadata = url.get_metadata().await?;
+                self.metadata_cache.insert(video_id, metadata.clone());
+                metadata
+            }
+        };
 
-        self.queue.push_back(QueuedSong {
-            start_time: new_start_time,
-            duration,
-            link: url,
-        });
+        self.queue
+            .push_back(QueuedSong::new(new_start_time, url, metadata, submitter));
 
         Ok(())
     }
+
+    /// Removes the first queued song matching `video_id`, if any. If it was
+    /// the currently playing song, it's moved to history (as if it had just
+    /// finished) and the skip vote tally resets.
+    fn remove_from_queue(&mut self, video_id: &str) {
+        let Some(pos) = self
+            .queue
+            .iter()
+            .position(|song| song.link.video_id() == video_id)
+        else {
+            return;
+        };
+
+        let front_removed = pos == 0;
+        if let Some(removed) = self.queue.remove(pos) {
+            if front_removed {
+                self.history.push_back(removed);
+                self.skip_votes.clear();
+            }
+        }
+
+        self.reschedule(front_removed);
+    }
+
+    /// Skips the currently playing song, moving it to history early.
+    fn skip_front(&mut self) {
+        if let Some(song) = self.queue.pop_front() {
+            self.history.push_back(song);
+            self.skip_votes.clear();
+            self.reschedule(true);
+        }
+    }
+
+    /// Tallies a distinct vote to skip the front song, auto-skipping once
+    /// `skip_vote_threshold` distinct submitters have voted.
+    fn vote_skip(&mut self, submitter: Vec<u8>) {
+        if self.queue.is_empty() {
+            return;
+        }
+
+        self.skip_votes.insert(submitter);
+        if self.skip_votes.len() >= self.skip_vote_threshold {
+            self.skip_front();
+        }
+    }
+
+    /// Re-stamps contiguous start times across the queue so the schedule
+    /// has no gaps after a removal. When `front_changed` the new front
+    /// starts now; otherwise the existing front's start time is kept and
+    /// only the songs behind it shift earlier.
+    fn reschedule(&mut self, front_changed: bool) {
+        let mut cursor = if front_changed {
+            self.current_time
+        } else {
+            self.queue
+                .front()
+                .map(|song| song.start_time)
+                .unwrap_or(self.current_time)
+        };
+
+        for song in self.queue.iter_mut() {
+            song.start_time = cursor;
+            cursor += song.duration;
+        }
+    }
 }
@@ -1,18 +1,178 @@
-// use ed25519_dalek::{Signature as Ed25519Signature, Verifier, VerifyingKey};
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use log::warn;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::time::Duration;
+use tokio::process::Command;
 
+/// The operation a [`Transaction`] carries out, independent of who signed it.
 #[derive(Clone, Deserialize, Serialize)]
-pub enum Transaction {
+pub enum TransactionPayload {
     AddToQueue { url: YoutubeLink },
+    /// Removes the first queued song with this video ID, wherever it sits
+    /// in the queue.
+    RemoveFromQueue { video_id: String },
+    /// Immediately skips the currently playing song.
+    Skip,
+    /// Casts one vote to skip the currently playing song. `State` tallies
+    /// distinct submitters and auto-skips once a threshold is reached.
+    VoteSkip,
+}
+
+/// A signed transaction envelope. `signature` covers the canonically
+/// serialized `payload`, so a node can attribute and authenticate a
+/// transaction before it's allowed to mutate the queue.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Transaction {
+    pub payload: TransactionPayload,
+    pub pubkey: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl Transaction {
+    /// Signs `payload` with `signing_key`, producing a ready-to-submit
+    /// transaction envelope.
+    pub fn sign(payload: TransactionPayload, signing_key: &SigningKey) -> Result<Self> {
+        let encoded = serde_json::to_vec(&payload)?;
+        let signature = signing_key.sign(&encoded);
+
+        Ok(Transaction {
+            payload,
+            pubkey: signing_key.verifying_key().to_bytes().to_vec(),
+            signature: signature.to_bytes().to_vec(),
+        })
+    }
+
+    /// Verifies the envelope's signature over its canonically serialized
+    /// payload, returning the submitter's verifying key on success.
+    pub fn verify(&self) -> Result<VerifyingKey> {
+        let encoded = serde_json::to_vec(&self.payload)?;
+
+        let pubkey_bytes: [u8; 32] = self
+            .pubkey
+            .as_slice()
+            .try_into()
+            .map_err(|_| anyhow!("invalid public key length"))?;
+        let verifying_key = VerifyingKey::from_bytes(&pubkey_bytes)
+            .map_err(|e| anyhow!("invalid public key: {e}"))?;
+
+        let signature_bytes: [u8; 64] = self
+            .signature
+            .as_slice()
+            .try_into()
+            .map_err(|_| anyhow!("invalid signature length"))?;
+        let signature = Signature::from_bytes(&signature_bytes);
+
+        verifying_key
+            .verify(&encoded, &signature)
+            .map_err(|e| anyhow!("signature verification failed: {e}"))?;
+
+        Ok(verifying_key)
+    }
 }
 
 #[derive(Clone, Deserialize, Serialize)]
 pub struct YoutubeLink(String);
 
+/// Metadata describing a YouTube video, as returned by a [`MetadataProvider`].
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct VideoMetadata {
+    pub duration: Duration,
+    pub title: Option<String>,
+    pub uploader: Option<String>,
+    pub thumbnail: Option<String>,
+    pub live_status: Option<String>,
+}
+
+/// A source of [`VideoMetadata`] for a [`YoutubeLink`].
+///
+/// `YtDlpProvider` is tried first since it doesn't depend on scraping
+/// YouTube's page markup; `ScrapeProvider` is kept around as a fallback for
+/// hosts where the `yt-dlp` binary isn't installed.
+#[allow(async_fn_in_trait)]
+pub trait MetadataProvider {
+    async fn fetch(&self, link: &YoutubeLink) -> Result<VideoMetadata>;
+}
+
+pub struct YtDlpProvider;
+
+impl MetadataProvider for YtDlpProvider {
+    async fn fetch(&self, link: &YoutubeLink) -> Result<VideoMetadata> {
+        let output = Command::new("yt-dlp")
+            .args(["--dump-single-json", "--skip-download", &link.0])
+            .output()
+            .await
+            .map_err(|e| anyhow!("failed to run yt-dlp: {e}"))?;
+
+        if !output.status.success() {
+            bail!(
+                "yt-dlp exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        let json: Value = serde_json::from_slice(&output.stdout)?;
+        let seconds = json["duration"]
+            .as_f64()
+            .ok_or_else(|| anyhow!("yt-dlp output missing duration"))?;
+
+        Ok(VideoMetadata {
+            duration: Duration::from_secs_f64(seconds),
+            title: json["title"].as_str().map(str::to_string),
+            uploader: json["uploader"].as_str().map(str::to_string),
+            thumbnail: json["thumbnail"].as_str().map(str::to_string),
+            live_status: json["live_status"].as_str().map(str::to_string),
+        })
+    }
+}
+
+pub struct ScrapeProvider;
+
+impl MetadataProvider for ScrapeProvider {
+    async fn fetch(&self, link: &YoutubeLink) -> Result<VideoMetadata> {
+        let client = reqwest::Client::new();
+
+        let response = client
+            .get(&link.0)
+            .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
+            .send()
+            .await?
+            .text()
+            .await?;
+
+        let re = Regex::new(r#"ytInitialPlayerResponse\s*=\s*(\{.+?\}\});"#).unwrap();
+        let json_str = re
+            .captures(&response)
+            .and_then(|caps| caps.get(1))
+            .ok_or_else(|| anyhow!("Could not find ytInitialPlayerResponse"))?
+            .as_str();
+
+        let json: Value = serde_json::from_str(json_str)?;
+        let details = &json["videoDetails"];
+        let seconds_str = details["lengthSeconds"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Could not find duration"))?;
+        let seconds: u64 = seconds_str.parse()?;
+
+        Ok(VideoMetadata {
+            duration: Duration::from_secs(seconds),
+            title: details["title"].as_str().map(str::to_string),
+            uploader: details["author"].as_str().map(str::to_string),
+            thumbnail: details["thumbnail"]["thumbnails"]
+                .as_array()
+                .and_then(|thumbs| thumbs.last())
+                .and_then(|thumb| thumb["url"].as_str())
+                .map(str::to_string),
+            live_status: details["isLiveContent"]
+                .as_bool()
+                .map(|live| if live { "is_live" } else { "not_live" }.to_string()),
+        })
+    }
+}
+
 #[allow(dead_code)]
 impl YoutubeLink {
     /// Creates a new YoutubeLink from a URL string.
@@ -40,38 +200,116 @@ impl YoutubeLink {
         &self.0
     }
 
+    /// The 11-character canonical YouTube video ID, e.g. for use as a cache
+    /// key. Always present since `new` only ever builds canonical URLs.
+    pub fn video_id(&self) -> &str {
+        self.0
+            .rsplit('=')
+            .next()
+            .expect("canonical YoutubeLink always contains a video id")
+    }
+
+    /// Fetches metadata for this video, preferring `yt-dlp` and falling back
+    /// to scraping the watch page's embedded player response if the binary
+    /// isn't available on this host.
+    pub async fn get_metadata(&self) -> Result<VideoMetadata> {
+        match YtDlpProvider.fetch(self).await {
+            Ok(metadata) => Ok(metadata),
+            Err(e) => {
+                warn!("yt-dlp metadata fetch failed ({e}), falling back to HTML scrape");
+                ScrapeProvider.fetch(self).await
+            }
+        }
+    }
+
     pub async fn get_video_duration(&self) -> Result<Duration> {
-        // Create HTTP client
-        let client = reqwest::Client::new();
+        Ok(self.get_metadata().await?.duration)
+    }
+}
 
-        // Fetch video page
-        let response = client
-            .get(&self.0)
-            .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
-            .send()
-            .await?
-            .text()
-            .await?;
+/// Default cap on how many videos a single playlist or search request may
+/// expand into. Overridable by callers that resolve a [`PlaylistLink`] or
+/// [`SearchQuery`].
+pub const DEFAULT_ENQUEUE_CAP: usize = 25;
 
-        let re = Regex::new(r#"ytInitialPlayerResponse\s*=\s*(\{.+?\}\});"#).unwrap();
-        let json_str = re
-            .captures(&response)
-            .and_then(|caps| caps.get(1))
-            .ok_or_else(|| anyhow!("Could not find ytInitialPlayerResponse"))?
-            .as_str();
+/// A YouTube playlist URL, resolved via `yt-dlp` into its member videos.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct PlaylistLink(String);
 
-        // Parse JSON and extract duration
-        let json: Value = serde_json::from_str(json_str)?;
-        let seconds_str = json["videoDetails"]["lengthSeconds"]
-            .as_str()
-            .ok_or_else(|| anyhow!("Could not find duration"))?;
+#[allow(dead_code)]
+impl PlaylistLink {
+    /// Accepts any URL containing a `list=` query parameter, e.g.
+    /// `https://www.youtube.com/playlist?list=PLAYLIST_ID`.
+    pub fn new(url: String) -> Result<Self> {
+        let cleaned = url.trim().replace('\\', "");
+        if !cleaned.contains("list=") {
+            bail!("Not a YouTube playlist URL: {}", cleaned);
+        }
+        Ok(Self(cleaned))
+    }
 
-        // Parse seconds and create Duration
-        let seconds: u64 = seconds_str.parse()?;
-        Ok(Duration::from_secs(seconds))
+    /// Expands the playlist into its member videos, in playlist order,
+    /// capped at `cap` entries.
+    pub async fn resolve(&self, cap: usize) -> Result<Vec<YoutubeLink>> {
+        resolve_video_ids(&self.0, cap).await
+    }
+}
+
+/// A free-text search query, resolved via `yt-dlp`'s `ytsearch` pseudo-URL
+/// into matching videos.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct SearchQuery(String);
+
+#[allow(dead_code)]
+impl SearchQuery {
+    pub fn new(query: String) -> Result<Self> {
+        let cleaned = query.trim().to_string();
+        if cleaned.is_empty() {
+            bail!("Empty search query");
+        }
+        Ok(Self(cleaned))
+    }
+
+    /// Runs the search and returns up to `cap` matching videos, in
+    /// relevance order.
+    pub async fn resolve(&self, cap: usize) -> Result<Vec<YoutubeLink>> {
+        let search_spec = format!("ytsearch{}:{}", cap, self.0);
+        resolve_video_ids(&search_spec, cap).await
     }
 }
 
+/// Runs `yt-dlp --flat-playlist --dump-single-json <target>` and extracts
+/// the video IDs of up to `cap` entries. Used for both playlist expansion
+/// and search, which yt-dlp represents the same way: a JSON object with an
+/// `entries` array.
+async fn resolve_video_ids(target: &str, cap: usize) -> Result<Vec<YoutubeLink>> {
+    let output = Command::new("yt-dlp")
+        .args(["--flat-playlist", "--dump-single-json", target])
+        .output()
+        .await
+        .map_err(|e| anyhow!("failed to run yt-dlp: {e}"))?;
+
+    if !output.status.success() {
+        bail!(
+            "yt-dlp exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let json: Value = serde_json::from_slice(&output.stdout)?;
+    let entries = json["entries"]
+        .as_array()
+        .ok_or_else(|| anyhow!("yt-dlp output missing entries"))?;
+
+    entries
+        .iter()
+        .filter_map(|entry| entry["id"].as_str())
+        .take(cap)
+        .map(|id| YoutubeLink::new(id.to_string()))
+        .collect()
+}
+
 /// Extract video ID from various YouTube URL formats
 fn extract_video_id(url: &str) -> Result<String> {
     // Regular expressions for different YouTube URL formats
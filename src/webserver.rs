@@ -1,15 +1,16 @@
 use crate::fullnode::FullNode;
 use crate::state::QueuedSong;
-use crate::tx::{Transaction, YoutubeLink};
-use axum::{extract::State as AxumState, http::StatusCode, Json};
-use serde::Deserialize;
+use crate::tx::{Transaction, TransactionPayload};
+use axum::{
+    extract::State as AxumState,
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
 use std::collections::VecDeque;
 use std::sync::Arc;
-
-#[derive(Deserialize)]
-pub(crate) struct AddSongRequest {
-    url: YoutubeLink,
-}
+use std::time::{Duration, SystemTime};
 
 pub(crate) async fn get_queue(
     AxumState(node): AxumState<Arc<FullNode>>,
@@ -27,10 +28,125 @@ pub(crate) async fn get_history(
 
 pub(crate) async fn send_tx(
     AxumState(node): AxumState<Arc<FullNode>>,
-    Json(payload): Json<AddSongRequest>,
+    Json(tx): Json<Transaction>,
+) -> Result<(), (StatusCode, String)> {
+    node.queue_transaction(tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+pub(crate) async fn skip(
+    AxumState(node): AxumState<Arc<FullNode>>,
+    Json(tx): Json<Transaction>,
+) -> Result<(), (StatusCode, String)> {
+    if !matches!(tx.payload, TransactionPayload::Skip) {
+        return Err((StatusCode::BAD_REQUEST, "expected a Skip transaction".into()));
+    }
+
+    node.queue_transaction(tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+pub(crate) async fn remove(
+    AxumState(node): AxumState<Arc<FullNode>>,
+    Json(tx): Json<Transaction>,
+) -> Result<(), (StatusCode, String)> {
+    if !matches!(tx.payload, TransactionPayload::RemoveFromQueue { .. }) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "expected a RemoveFromQueue transaction".into(),
+        ));
+    }
+
+    node.queue_transaction(tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+pub(crate) async fn vote_skip(
+    AxumState(node): AxumState<Arc<FullNode>>,
+    Json(tx): Json<Transaction>,
 ) -> Result<(), (StatusCode, String)> {
-    let tx = Transaction::AddToQueue { url: payload.url };
+    if !matches!(tx.payload, TransactionPayload::VoteSkip) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "expected a VoteSkip transaction".into(),
+        ));
+    }
+
     node.queue_transaction(tx)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
 }
+
+/// Renders history (already played) followed by the queue (up next) as an
+/// RSS 2.0 feed, so the "now playing / up next" history can be followed
+/// from any feed reader instead of polling `/channels`.
+pub(crate) async fn get_feed(AxumState(node): AxumState<Arc<FullNode>>) -> impl IntoResponse {
+    let state = node.state.lock().await;
+    let items: String = state
+        .get_history()
+        .iter()
+        .chain(state.get_queue().iter())
+        .map(feed_item)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let body = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd">
+  <channel>
+    <title>celestia-jukebox</title>
+    <link>https://github.com/distractedm1nd/celestia-jukebox</link>
+    <description>Now playing and up next, as recorded on Celestia DA</description>
+{items}
+  </channel>
+</rss>"#,
+    );
+
+    ([(header::CONTENT_TYPE, "application/rss+xml")], body)
+}
+
+fn feed_item(song: &QueuedSong) -> String {
+    let title = song
+        .title
+        .clone()
+        .unwrap_or_else(|| song.link.as_str().to_string());
+    let link = escape_xml(song.link.as_str());
+
+    format!(
+        r#"    <item>
+      <title>{title}</title>
+      <link>{link}</link>
+      <guid>{link}</guid>
+      <pubDate>{pub_date}</pubDate>
+      <itunes:duration>{duration}</itunes:duration>
+    </item>"#,
+        title = escape_xml(&title),
+        pub_date = rfc2822(song.start_time),
+        duration = format_duration(song.duration),
+    )
+}
+
+fn rfc2822(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).to_rfc2822()
+}
+
+fn format_duration(duration: Duration) -> String {
+    let total_secs = duration.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}",
+        total_secs / 3600,
+        (total_secs % 3600) / 60,
+        total_secs % 60
+    )
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}